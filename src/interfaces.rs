@@ -0,0 +1,102 @@
+use std::fmt;
+
+/// Identifies a single repo (peer) participating in the sync protocol.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct RepoId(pub String);
+
+impl From<&str> for RepoId {
+    fn from(s: &str) -> Self {
+        Self(s.to_string())
+    }
+}
+
+impl fmt::Display for RepoId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+/// Identifies an automerge document being synced between repos.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct DocumentId(pub String);
+
+impl From<&str> for DocumentId {
+    fn from(s: &str) -> Self {
+        Self(s.to_string())
+    }
+}
+
+impl fmt::Display for DocumentId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+/// A message addressed to a particular repo, scoped to a single document.
+#[derive(Debug, Clone)]
+pub enum RepoMessage {
+    Sync {
+        from_repo_id: RepoId,
+        to_repo_id: RepoId,
+        document_id: DocumentId,
+        message: Vec<u8>,
+    },
+    /// Transient presence/cursor/awareness data, gossiped to peers but never
+    /// written to `Storage`. `session_id`/`count` identify this message for
+    /// dedup when `broadcast` causes it to be re-forwarded across a mesh;
+    /// `count` increases monotonically within a session.
+    Ephemeral {
+        from_repo_id: RepoId,
+        to_repo_id: RepoId,
+        document_id: DocumentId,
+        session_id: String,
+        count: u64,
+        broadcast: bool,
+        message: Vec<u8>,
+    },
+}
+
+/// The top level wire message exchanged between connected repos.
+#[derive(Debug, Clone)]
+pub enum Message {
+    /// Handshake message a connecting repo sends first. `protocol_version` is the
+    /// highest version this side speaks; `capabilities` lists optional features
+    /// such as `"chunked-bodies"`, negotiated alongside the version.
+    Join {
+        repo_id: RepoId,
+        protocol_version: u32,
+        capabilities: Vec<String>,
+    },
+    /// Handshake reply, mirroring `Join`'s version/capability fields.
+    Joined {
+        repo_id: RepoId,
+        protocol_version: u32,
+        capabilities: Vec<String>,
+    },
+    Repo(RepoMessage),
+    /// One ordered piece of a `Sync` body that was too large to send as a single
+    /// `Repo` message. The decoder reassembles these, keyed by `(from_repo_id,
+    /// document_id)`, into a single `RepoMessage::Sync` once `more` is `false`.
+    Chunk {
+        from_repo_id: RepoId,
+        to_repo_id: RepoId,
+        document_id: DocumentId,
+        seq: u32,
+        more: bool,
+        message: Vec<u8>,
+    },
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum NetworkError {
+    #[error("unexpected message during handshake")]
+    UnexpectedHandshakeMessage,
+    #[error("unexpected message type")]
+    UnexpectedMessage,
+    #[error("protocol version mismatch: we support {ours}, peer supports {theirs}")]
+    VersionMismatch { ours: u32, theirs: u32 },
+    #[error("connection closed during handshake")]
+    ConnectionClosedDuringHandshake,
+    #[error(transparent)]
+    Protocol(#[from] crate::network_connect::DecodeError),
+}
@@ -0,0 +1,7 @@
+pub mod interfaces;
+pub mod network_connect;
+pub mod repo;
+
+pub use interfaces::{DocumentId, Message, NetworkError, RepoId, RepoMessage};
+pub use network_connect::{CodecError, ConnDirection, DecodeError};
+pub use repo::RepoHandle;
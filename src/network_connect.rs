@@ -2,10 +2,33 @@ use crate::interfaces::{DocumentId, Message, NetworkError, RepoId, RepoMessage};
 use crate::repo::RepoHandle;
 use bytes::{Buf, BytesMut};
 use futures::{SinkExt, StreamExt};
+use std::collections::HashMap;
 use tokio::io::{AsyncRead, AsyncWrite};
 use tokio::net::ToSocketAddrs;
 use tokio_util::codec::{Decoder, Encoder};
 
+/// Sync bodies larger than this are split into chunks on the wire by default.
+const DEFAULT_CHUNK_SIZE: usize = 16 * 1024;
+
+/// A single connection can't make us buffer more than this for one frame by default.
+const DEFAULT_MAX_FRAME_LENGTH: usize = 64 * 1024 * 1024;
+
+/// A single connection can't have more than this many chunked `Sync` bodies in
+/// flight at once by default, so a peer can't multiply `max_frame_length` by
+/// opening unlimited distinct `(from_repo_id, document_id)` assemblies that
+/// never finish.
+const DEFAULT_MAX_CONCURRENT_ASSEMBLIES: usize = 64;
+
+/// The highest wire protocol version this build speaks.
+pub(crate) const PROTOCOL_VERSION: u32 = 2;
+
+/// Connections negotiating below this version are refused outright.
+pub(crate) const MIN_SUPPORTED_PROTOCOL_VERSION: u32 = 1;
+
+/// Advertised during the handshake so a peer on the same `protocol_version` knows
+/// whether it's safe to send us `Chunk` frames.
+pub(crate) const CAP_CHUNKED_BODIES: &str = "chunked-bodies";
+
 /// Which direction a connection passed to [`Repo::connect`] is going
 pub enum ConnDirection {
     Incoming,
@@ -24,38 +47,66 @@ impl RepoHandle {
         Io: AsyncRead + AsyncWrite + Send + 'static,
         Source: ToSocketAddrs,
     {
-        let codec = Codec::new();
-        let framed = tokio_util::codec::Framed::new(io, codec);
-        let (mut sink, mut stream) = framed.split();
+        let codec = Codec::builder()
+            .max_frame_length(DEFAULT_MAX_FRAME_LENGTH)
+            .build();
+        let mut framed = tokio_util::codec::Framed::new(io, codec);
+
+        let our_capabilities = || vec![CAP_CHUNKED_BODIES.to_string()];
 
-        let other_id = match direction {
+        let (other_id, negotiated_version, their_capabilities) = match direction {
             ConnDirection::Incoming => {
-                if let Some(msg) = stream.next().await {
-                    let other_id = match msg {
-                        Ok(Message::Join(other_id)) => other_id,
-                        _ => return Err(NetworkError::Error.into()),
-                    };
-                    let msg = Message::Joined(self.get_repo_id().clone());
-                    sink.send(msg).await?;
-                    other_id
-                } else {
-                    return Err(NetworkError::Error.into());
-                }
+                let (other_id, their_version, their_capabilities) = match framed.next().await {
+                    Some(Ok(Message::Join {
+                        repo_id,
+                        protocol_version,
+                        capabilities,
+                    })) => (repo_id, protocol_version, capabilities),
+                    Some(_) => return Err(NetworkError::UnexpectedHandshakeMessage.into()),
+                    None => return Err(NetworkError::ConnectionClosedDuringHandshake.into()),
+                };
+                let negotiated_version = negotiate_version(PROTOCOL_VERSION, their_version)?;
+                let msg = Message::Joined {
+                    repo_id: self.get_repo_id().clone(),
+                    protocol_version: PROTOCOL_VERSION,
+                    capabilities: our_capabilities(),
+                };
+                framed.send(msg).await?;
+                (other_id, negotiated_version, their_capabilities)
             }
             ConnDirection::Outgoing => {
-                let msg = Message::Join(self.get_repo_id().clone());
-                sink.send(msg).await?;
-                if let Some(Ok(Message::Joined(other_id))) = stream.next().await {
-                    other_id
-                } else {
-                    return Err(NetworkError::Error.into());
-                }
+                let msg = Message::Join {
+                    repo_id: self.get_repo_id().clone(),
+                    protocol_version: PROTOCOL_VERSION,
+                    capabilities: our_capabilities(),
+                };
+                framed.send(msg).await?;
+                let (other_id, their_version, their_capabilities) = match framed.next().await {
+                    Some(Ok(Message::Joined {
+                        repo_id,
+                        protocol_version,
+                        capabilities,
+                    })) => (repo_id, protocol_version, capabilities),
+                    Some(_) => return Err(NetworkError::UnexpectedHandshakeMessage.into()),
+                    None => return Err(NetworkError::ConnectionClosedDuringHandshake.into()),
+                };
+                let negotiated_version = negotiate_version(PROTOCOL_VERSION, their_version)?;
+                (other_id, negotiated_version, their_capabilities)
             }
         };
 
+        // Only split large `Sync` bodies into `Chunk` frames if the peer told us
+        // (via the handshake) that it knows how to reassemble them.
+        let chunking_enabled = their_capabilities
+            .iter()
+            .any(|cap| cap == CAP_CHUNKED_BODIES);
+        framed.codec_mut().set_chunking_enabled(chunking_enabled);
+
+        let (sink, stream) = framed.split();
+
         let stream = stream.map(|msg| match msg {
             Ok(Message::Repo(repo_msg)) => Ok(repo_msg),
-            _ => Err(NetworkError::Error),
+            _ => Err(NetworkError::UnexpectedMessage),
         });
 
         let sink = sink.with(|msg: Result<RepoMessage, NetworkError>| match msg {
@@ -63,18 +114,130 @@ impl RepoHandle {
             Err(err) => futures::future::ready(Err(err)),
         });
 
-        self.new_remote_repo(other_id, Box::new(stream), Box::new(sink));
+        self.new_remote_repo(
+            other_id,
+            negotiated_version,
+            Box::new(stream),
+            Box::new(sink),
+        );
 
         Ok(())
     }
 }
 
-/// A simple length prefixed codec over `crate::Message` for use over stream oriented transports
-pub(crate) struct Codec;
+/// Picks the minimum mutually-supported protocol version, refusing anything
+/// below [`MIN_SUPPORTED_PROTOCOL_VERSION`].
+fn negotiate_version(ours: u32, theirs: u32) -> Result<u32, NetworkError> {
+    let negotiated = ours.min(theirs);
+    if negotiated < MIN_SUPPORTED_PROTOCOL_VERSION {
+        return Err(NetworkError::VersionMismatch {
+            ours,
+            theirs,
+        });
+    }
+    Ok(negotiated)
+}
+
+/// Tracks the chunks received so far for a `(from_repo_id, document_id)` pair
+/// while a multi-chunk `Sync` body is still in flight.
+#[derive(Debug)]
+struct ChunkAssembly {
+    to_repo_id: RepoId,
+    next_seq: u32,
+    message: Vec<u8>,
+}
+
+/// A length prefixed codec over `crate::Message` for use over stream oriented transports.
+///
+/// Large `Sync` bodies are transparently split into `Chunk` frames on encode, and
+/// reassembled on decode, so a single big document is never sent as one huge frame; the
+/// decoder still collects the chunks into one buffer holding the whole body.
+/// A `max_frame_length` ceiling also keeps a hostile or buggy peer's length prefix
+/// from making us reserve unbounded memory before any payload has even arrived.
+pub(crate) struct Codec {
+    chunk_size: usize,
+    max_frame_length: usize,
+    /// Whether we're allowed to emit `Chunk` frames on this connection. Starts `true`
+    /// and is narrowed to the peer's negotiated capabilities once the handshake
+    /// completes (see `Codec::set_chunking_enabled`), so we never split a `Sync` body
+    /// for a peer that never advertised `CAP_CHUNKED_BODIES`.
+    chunking_enabled: bool,
+    /// Caps how many distinct `(from_repo_id, document_id)` assemblies can be in
+    /// flight at once, so `assemblies` can't grow to `max_frame_length` times an
+    /// unbounded number of keys.
+    max_concurrent_assemblies: usize,
+    assemblies: HashMap<(RepoId, DocumentId), ChunkAssembly>,
+}
 
 impl Codec {
     pub(crate) fn new() -> Self {
-        Self
+        Self::builder().build()
+    }
+
+    pub(crate) fn with_chunk_size(chunk_size: usize) -> Self {
+        Self::builder().chunk_size(chunk_size).build()
+    }
+
+    pub(crate) fn builder() -> CodecBuilder {
+        CodecBuilder::default()
+    }
+
+    /// Called once the handshake has negotiated whether the peer understands
+    /// chunked `Sync` bodies; large payloads are sent as a single frame instead
+    /// when this is `false`.
+    pub(crate) fn set_chunking_enabled(&mut self, enabled: bool) {
+        self.chunking_enabled = enabled;
+    }
+}
+
+/// Builder for [`Codec`], so callers only need to override the defaults they care about.
+pub(crate) struct CodecBuilder {
+    chunk_size: usize,
+    max_frame_length: usize,
+    chunking_enabled: bool,
+    max_concurrent_assemblies: usize,
+}
+
+impl Default for CodecBuilder {
+    fn default() -> Self {
+        Self {
+            chunk_size: DEFAULT_CHUNK_SIZE,
+            max_frame_length: DEFAULT_MAX_FRAME_LENGTH,
+            chunking_enabled: true,
+            max_concurrent_assemblies: DEFAULT_MAX_CONCURRENT_ASSEMBLIES,
+        }
+    }
+}
+
+impl CodecBuilder {
+    pub(crate) fn chunk_size(mut self, chunk_size: usize) -> Self {
+        self.chunk_size = chunk_size;
+        self
+    }
+
+    pub(crate) fn max_frame_length(mut self, max_frame_length: usize) -> Self {
+        self.max_frame_length = max_frame_length;
+        self
+    }
+
+    pub(crate) fn chunking_enabled(mut self, chunking_enabled: bool) -> Self {
+        self.chunking_enabled = chunking_enabled;
+        self
+    }
+
+    pub(crate) fn max_concurrent_assemblies(mut self, max_concurrent_assemblies: usize) -> Self {
+        self.max_concurrent_assemblies = max_concurrent_assemblies;
+        self
+    }
+
+    pub(crate) fn build(self) -> Codec {
+        Codec {
+            chunk_size: self.chunk_size,
+            max_frame_length: self.max_frame_length,
+            chunking_enabled: self.chunking_enabled,
+            max_concurrent_assemblies: self.max_concurrent_assemblies,
+            assemblies: HashMap::new(),
+        }
     }
 }
 
@@ -89,8 +252,12 @@ pub enum CodecError {
 }
 
 impl From<CodecError> for NetworkError {
-    fn from(_err: CodecError) -> Self {
-        NetworkError::Error
+    fn from(err: CodecError) -> Self {
+        match err {
+            CodecError::Io(_) => NetworkError::ConnectionClosedDuringHandshake,
+            CodecError::Decode(e) => NetworkError::Protocol(e),
+            CodecError::Network(e) => e,
+        }
     }
 }
 
@@ -108,16 +275,97 @@ impl Decoder for Codec {
         len_bytes.copy_from_slice(&src[..4]);
         let len = u32::from_be_bytes(len_bytes) as usize;
 
-        // Check if we have enough data for this message
-        if src.len() < len + 4 {
-            src.reserve(len + 4 - src.len());
+        if len > self.max_frame_length {
+            return Err(DecodeError::FrameTooLarge {
+                len,
+                max: self.max_frame_length,
+            }
+            .into());
+        }
+
+        // Check if we have enough data for this message. Reserve only what's
+        // still missing (capped by `max_frame_length`, already checked above)
+        // instead of trusting the whole prefix up front.
+        let needed = len + 4;
+        if src.len() < needed {
+            let missing = needed - src.len();
+            src.reserve(std::cmp::min(missing, self.max_frame_length));
             return Ok(None);
         }
 
         // Parse the message
         let data = src[4..len + 4].to_vec();
         src.advance(len + 4);
-        Message::decode(&data).map(Some).map_err(Into::into)
+        let msg = Message::decode(&data)?;
+
+        match msg {
+            Message::Chunk {
+                from_repo_id,
+                to_repo_id,
+                document_id,
+                seq,
+                more,
+                message,
+            } => {
+                if message.len() > self.chunk_size {
+                    return Err(DecodeError::ChunkTooLarge {
+                        len: message.len(),
+                        max: self.chunk_size,
+                    }
+                    .into());
+                }
+
+                let key = (from_repo_id, document_id);
+                if !self.assemblies.contains_key(&key)
+                    && self.assemblies.len() >= self.max_concurrent_assemblies
+                {
+                    return Err(DecodeError::TooManyConcurrentAssemblies {
+                        max: self.max_concurrent_assemblies,
+                    }
+                    .into());
+                }
+                {
+                    let assembly = self.assemblies.entry(key.clone()).or_insert(ChunkAssembly {
+                        to_repo_id: to_repo_id.clone(),
+                        next_seq: 0,
+                        message: Vec::new(),
+                    });
+
+                    if seq != assembly.next_seq {
+                        let (expected, actual) = (assembly.next_seq, seq);
+                        self.assemblies.remove(&key);
+                        return Err(DecodeError::OutOfOrderChunk { expected, actual }.into());
+                    }
+
+                    if assembly.message.len() + message.len() > self.max_frame_length {
+                        let len = assembly.message.len() + message.len();
+                        self.assemblies.remove(&key);
+                        return Err(DecodeError::ChunkTooLarge {
+                            len,
+                            max: self.max_frame_length,
+                        }
+                        .into());
+                    }
+
+                    assembly.next_seq += 1;
+                    assembly.message.extend_from_slice(&message);
+                }
+
+                if more {
+                    Ok(None)
+                } else {
+                    let assembly = self.assemblies.remove(&key).unwrap();
+                    let (from_repo_id, document_id) = key;
+                    Ok(Some(Message::Repo(RepoMessage::Sync {
+                        from_repo_id,
+                        to_repo_id: assembly.to_repo_id,
+                        document_id,
+                        message: assembly.message,
+                    })))
+                }
+            }
+            other => Ok(Some(other)),
+        }
     }
 }
 
@@ -125,16 +373,44 @@ impl Encoder<Message> for Codec {
     type Error = CodecError;
 
     fn encode(&mut self, msg: Message, dst: &mut BytesMut) -> Result<(), Self::Error> {
-        let encoded = msg.encode();
-        let len = encoded.len() as u32;
-        let len_slice = len.to_be_bytes();
-        dst.reserve(4 + len as usize);
-        dst.extend_from_slice(&len_slice);
-        dst.extend_from_slice(&encoded);
-        Ok(())
+        match msg {
+            Message::Repo(RepoMessage::Sync {
+                from_repo_id,
+                to_repo_id,
+                document_id,
+                message,
+            }) if self.chunking_enabled && message.len() > self.chunk_size => {
+                let chunks: Vec<&[u8]> = message.chunks(self.chunk_size).collect();
+                let last = chunks.len().saturating_sub(1);
+                for (seq, chunk) in chunks.into_iter().enumerate() {
+                    let frame = Message::Chunk {
+                        from_repo_id: from_repo_id.clone(),
+                        to_repo_id: to_repo_id.clone(),
+                        document_id: document_id.clone(),
+                        seq: seq as u32,
+                        more: seq != last,
+                        message: chunk.to_vec(),
+                    };
+                    write_frame(&frame, dst);
+                }
+                Ok(())
+            }
+            msg => {
+                write_frame(&msg, dst);
+                Ok(())
+            }
+        }
     }
 }
 
+fn write_frame(msg: &Message, dst: &mut BytesMut) {
+    let encoded = msg.encode();
+    let len = encoded.len() as u32;
+    dst.reserve(4 + len as usize);
+    dst.extend_from_slice(&len.to_be_bytes());
+    dst.extend_from_slice(&encoded);
+}
+
 impl Message {
     pub fn decode(data: &[u8]) -> Result<Self, DecodeError> {
         let mut decoder = minicbor::Decoder::new(data);
@@ -143,6 +419,14 @@ impl Message {
         let mut document_id: Option<DocumentId> = None;
         let mut type_name: Option<&str> = None;
         let mut message: Option<Vec<u8>> = None;
+        let mut seq: Option<u32> = None;
+        let mut more: Option<bool> = None;
+        let mut channel_id: Option<DocumentId> = None;
+        let mut session_id: Option<String> = None;
+        let mut count: Option<u64> = None;
+        let mut broadcast: Option<bool> = None;
+        let mut protocol_version: Option<u32> = None;
+        let mut capabilities: Option<Vec<String>> = None;
         let len = decoder.map()?.ok_or(DecodeError::MissingLen)?;
         for _ in 0..len {
             match decoder.str()? {
@@ -151,19 +435,59 @@ impl Message {
                 "documentId" => document_id = Some(decoder.str()?.into()),
                 "type" => type_name = Some(decoder.str()?),
                 "message" => message = Some(decoder.bytes()?.to_vec()),
+                "seq" => seq = Some(decoder.u32()?),
+                "more" => more = Some(decoder.bool()?),
+                "channelId" => channel_id = Some(decoder.str()?.into()),
+                "sessionId" => session_id = Some(decoder.str()?.to_string()),
+                "count" => count = Some(decoder.u64()?),
+                "broadcast" => broadcast = Some(decoder.bool()?),
+                "protocolVersion" => protocol_version = Some(decoder.u32()?),
+                "capabilities" => {
+                    let n = decoder.array()?.ok_or(DecodeError::MissingLen)?;
+                    let mut caps = Vec::with_capacity(n as usize);
+                    for _ in 0..n {
+                        caps.push(decoder.str()?.to_string());
+                    }
+                    capabilities = Some(caps);
+                }
                 _ => decoder.skip()?,
             }
         }
         match type_name {
             None => Err(DecodeError::MissingType),
-            Some("join") => Ok(Self::Join(sender_id.ok_or(DecodeError::MissingSenderId)?)),
+            Some("join") => Ok(Self::Join {
+                repo_id: sender_id.ok_or(DecodeError::MissingSenderId)?,
+                protocol_version: protocol_version.ok_or(DecodeError::MissingProtocolVersion)?,
+                capabilities: capabilities.unwrap_or_default(),
+            }),
             Some("message") => Ok(Self::Repo(RepoMessage::Sync {
                 from_repo_id: sender_id.ok_or(DecodeError::MissingSenderId)?,
                 to_repo_id: target_id.ok_or(DecodeError::MissingTargetId)?,
                 document_id: document_id.ok_or(DecodeError::MissingDocumentId)?,
                 message: message.ok_or(DecodeError::MissingData)?,
             })),
-            Some("joined") => Ok(Self::Joined(sender_id.ok_or(DecodeError::MissingSenderId)?)),
+            Some("joined") => Ok(Self::Joined {
+                repo_id: sender_id.ok_or(DecodeError::MissingSenderId)?,
+                protocol_version: protocol_version.ok_or(DecodeError::MissingProtocolVersion)?,
+                capabilities: capabilities.unwrap_or_default(),
+            }),
+            Some("chunk") => Ok(Self::Chunk {
+                from_repo_id: sender_id.ok_or(DecodeError::MissingSenderId)?,
+                to_repo_id: target_id.ok_or(DecodeError::MissingTargetId)?,
+                document_id: document_id.ok_or(DecodeError::MissingDocumentId)?,
+                seq: seq.ok_or(DecodeError::MissingSeq)?,
+                more: more.ok_or(DecodeError::MissingMore)?,
+                message: message.ok_or(DecodeError::MissingData)?,
+            }),
+            Some("ephemeral") => Ok(Self::Repo(RepoMessage::Ephemeral {
+                from_repo_id: sender_id.ok_or(DecodeError::MissingSenderId)?,
+                to_repo_id: target_id.ok_or(DecodeError::MissingTargetId)?,
+                document_id: channel_id.ok_or(DecodeError::MissingChannelId)?,
+                session_id: session_id.ok_or(DecodeError::MissingSessionId)?,
+                count: count.ok_or(DecodeError::MissingCount)?,
+                broadcast: broadcast.ok_or(DecodeError::MissingBroadcast)?,
+                message: message.ok_or(DecodeError::MissingData)?,
+            })),
             Some(other) => Err(DecodeError::UnknownType(other.to_string())),
         }
     }
@@ -172,12 +496,23 @@ impl Message {
         let out: Vec<u8> = Vec::new();
         let mut encoder = minicbor::Encoder::new(out);
         match self {
-            Self::Join(repo_id) => {
-                encoder.map(2).unwrap();
+            Self::Join {
+                repo_id,
+                protocol_version,
+                capabilities,
+            } => {
+                encoder.map(4).unwrap();
                 encoder.str("type").unwrap();
                 encoder.str("join").unwrap();
                 encoder.str("senderId").unwrap();
                 encoder.str(repo_id.0.as_str()).unwrap();
+                encoder.str("protocolVersion").unwrap();
+                encoder.u32(*protocol_version).unwrap();
+                encoder.str("capabilities").unwrap();
+                encoder.array(capabilities.len() as u64).unwrap();
+                for capability in capabilities {
+                    encoder.str(capability.as_str()).unwrap();
+                }
             }
             Self::Repo(RepoMessage::Sync {
                 from_repo_id,
@@ -197,14 +532,75 @@ impl Message {
                 encoder.str("message").unwrap();
                 encoder.bytes(message.as_slice()).unwrap();
             }
-            Self::Joined(repo_id) => {
-                encoder.map(2).unwrap();
+            Self::Joined {
+                repo_id,
+                protocol_version,
+                capabilities,
+            } => {
+                encoder.map(4).unwrap();
                 encoder.str("type").unwrap();
                 encoder.str("joined").unwrap();
                 encoder.str("senderId").unwrap();
                 encoder.str(repo_id.0.as_str()).unwrap();
+                encoder.str("protocolVersion").unwrap();
+                encoder.u32(*protocol_version).unwrap();
+                encoder.str("capabilities").unwrap();
+                encoder.array(capabilities.len() as u64).unwrap();
+                for capability in capabilities {
+                    encoder.str(capability.as_str()).unwrap();
+                }
+            }
+            Self::Chunk {
+                from_repo_id,
+                to_repo_id,
+                document_id,
+                seq,
+                more,
+                message,
+            } => {
+                encoder.map(7).unwrap();
+                encoder.str("type").unwrap();
+                encoder.str("chunk").unwrap();
+                encoder.str("senderId").unwrap();
+                encoder.str(from_repo_id.0.as_str()).unwrap();
+                encoder.str("targetId").unwrap();
+                encoder.str(to_repo_id.0.as_str()).unwrap();
+                encoder.str("documentId").unwrap();
+                encoder.str(document_id.0.as_str()).unwrap();
+                encoder.str("seq").unwrap();
+                encoder.u32(*seq).unwrap();
+                encoder.str("more").unwrap();
+                encoder.bool(*more).unwrap();
+                encoder.str("message").unwrap();
+                encoder.bytes(message.as_slice()).unwrap();
+            }
+            Self::Repo(RepoMessage::Ephemeral {
+                from_repo_id,
+                to_repo_id,
+                document_id,
+                session_id,
+                count,
+                broadcast,
+                message,
+            }) => {
+                encoder.map(8).unwrap();
+                encoder.str("type").unwrap();
+                encoder.str("ephemeral").unwrap();
+                encoder.str("senderId").unwrap();
+                encoder.str(from_repo_id.0.as_str()).unwrap();
+                encoder.str("targetId").unwrap();
+                encoder.str(to_repo_id.0.as_str()).unwrap();
+                encoder.str("channelId").unwrap();
+                encoder.str(document_id.0.as_str()).unwrap();
+                encoder.str("sessionId").unwrap();
+                encoder.str(session_id.as_str()).unwrap();
+                encoder.str("count").unwrap();
+                encoder.u64(*count).unwrap();
+                encoder.str("broadcast").unwrap();
+                encoder.bool(*broadcast).unwrap();
+                encoder.str("message").unwrap();
+                encoder.bytes(message.as_slice()).unwrap();
             }
-            _ => todo!(),
         }
         encoder.into_writer()
     }
@@ -230,6 +626,24 @@ pub enum DecodeError {
     MissingData,
     #[error("no broadcast field")]
     MissingBroadcast,
+    #[error("no seq field")]
+    MissingSeq,
+    #[error("no more field")]
+    MissingMore,
+    #[error("no session_id field")]
+    MissingSessionId,
+    #[error("no protocol_version field")]
+    MissingProtocolVersion,
+    #[error("no count field")]
+    MissingCount,
+    #[error("chunk out of order: expected seq {expected}, got {actual}")]
+    OutOfOrderChunk { expected: u32, actual: u32 },
+    #[error("chunk data length {len} exceeds limit {max}")]
+    ChunkTooLarge { len: usize, max: usize },
+    #[error("frame length {len} exceeds max frame length {max}")]
+    FrameTooLarge { len: usize, max: usize },
+    #[error("too many concurrent chunk assemblies, max {max}")]
+    TooManyConcurrentAssemblies { max: usize },
     #[error("unknown type {0}")]
     UnknownType(String),
 }
@@ -238,4 +652,160 @@ impl From<minicbor::decode::Error> for DecodeError {
     fn from(e: minicbor::decode::Error) -> Self {
         Self::Minicbor(e.to_string())
     }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rejects_oversized_length_prefix() {
+        let mut codec = Codec::builder().max_frame_length(1024).build();
+        let mut buf = BytesMut::new();
+        buf.extend_from_slice(&(2 * 1024 * 1024u32).to_be_bytes());
+
+        let err = codec.decode(&mut buf).unwrap_err();
+        assert!(matches!(
+            err,
+            CodecError::Decode(DecodeError::FrameTooLarge { len: 2097152, max: 1024 })
+        ));
+    }
+
+    #[test]
+    fn rejects_prefix_at_the_dos_extreme() {
+        let mut codec = Codec::builder().max_frame_length(64 * 1024 * 1024).build();
+        let mut buf = BytesMut::new();
+        buf.extend_from_slice(&0xFFFF_FFFFu32.to_be_bytes());
+
+        let err = codec.decode(&mut buf).unwrap_err();
+        assert!(matches!(err, CodecError::Decode(DecodeError::FrameTooLarge { .. })));
+        // We must not have eagerly reserved anywhere close to the claimed length.
+        assert!(buf.capacity() < 64 * 1024 * 1024);
+    }
+
+    #[test]
+    fn accepts_prefix_within_the_limit_and_awaits_more_data() {
+        let mut codec = Codec::builder().max_frame_length(1024).build();
+        let mut buf = BytesMut::new();
+        buf.extend_from_slice(&100u32.to_be_bytes());
+
+        assert!(codec.decode(&mut buf).unwrap().is_none());
+    }
+
+    #[test]
+    fn rejects_a_chunk_claiming_more_than_the_configured_chunk_size() {
+        let mut codec = Codec::builder().chunk_size(16).build();
+        let mut buf = BytesMut::new();
+        write_frame(
+            &Message::Chunk {
+                from_repo_id: RepoId::from("a"),
+                to_repo_id: RepoId::from("b"),
+                document_id: DocumentId::from("doc"),
+                seq: 0,
+                more: false,
+                message: vec![0u8; 32],
+            },
+            &mut buf,
+        );
+
+        let err = codec.decode(&mut buf).unwrap_err();
+        assert!(matches!(
+            err,
+            CodecError::Decode(DecodeError::ChunkTooLarge { len: 32, max: 16 })
+        ));
+    }
+
+    #[test]
+    fn rejects_an_assembly_that_would_grow_past_max_frame_length() {
+        // Each encoded chunk frame (170 bytes) fits under max_frame_length; only the
+        // assembled body (200 bytes) does not.
+        let mut codec = Codec::builder()
+            .chunk_size(100)
+            .max_frame_length(180)
+            .build();
+        let mut buf = BytesMut::new();
+        write_frame(
+            &Message::Chunk {
+                from_repo_id: RepoId::from("a"),
+                to_repo_id: RepoId::from("b"),
+                document_id: DocumentId::from("doc"),
+                seq: 0,
+                more: true,
+                message: vec![0u8; 100],
+            },
+            &mut buf,
+        );
+        assert!(codec.decode(&mut buf).unwrap().is_none());
+
+        write_frame(
+            &Message::Chunk {
+                from_repo_id: RepoId::from("a"),
+                to_repo_id: RepoId::from("b"),
+                document_id: DocumentId::from("doc"),
+                seq: 1,
+                more: false,
+                message: vec![0u8; 100],
+            },
+            &mut buf,
+        );
+
+        let err = codec.decode(&mut buf).unwrap_err();
+        assert!(matches!(
+            err,
+            CodecError::Decode(DecodeError::ChunkTooLarge { len: 200, max: 180 })
+        ));
+    }
+
+    #[test]
+    fn rejects_a_new_assembly_once_the_concurrent_assembly_cap_is_reached() {
+        let mut codec = Codec::builder()
+            .chunk_size(16)
+            .max_concurrent_assemblies(1)
+            .build();
+        let mut buf = BytesMut::new();
+        write_frame(
+            &Message::Chunk {
+                from_repo_id: RepoId::from("a"),
+                to_repo_id: RepoId::from("b"),
+                document_id: DocumentId::from("doc-1"),
+                seq: 0,
+                more: true,
+                message: vec![0u8; 4],
+            },
+            &mut buf,
+        );
+        assert!(codec.decode(&mut buf).unwrap().is_none());
+
+        write_frame(
+            &Message::Chunk {
+                from_repo_id: RepoId::from("a"),
+                to_repo_id: RepoId::from("b"),
+                document_id: DocumentId::from("doc-2"),
+                seq: 0,
+                more: true,
+                message: vec![0u8; 4],
+            },
+            &mut buf,
+        );
+
+        let err = codec.decode(&mut buf).unwrap_err();
+        assert!(matches!(
+            err,
+            CodecError::Decode(DecodeError::TooManyConcurrentAssemblies { max: 1 })
+        ));
+    }
+
+    #[test]
+    fn negotiate_version_picks_the_lower_of_the_two() {
+        assert_eq!(negotiate_version(PROTOCOL_VERSION, 1).unwrap(), 1);
+    }
+
+    #[test]
+    fn negotiate_version_rejects_a_peer_below_the_minimum_supported_version() {
+        let err = negotiate_version(PROTOCOL_VERSION, 0).unwrap_err();
+        assert!(matches!(
+            err,
+            NetworkError::VersionMismatch { ours: PROTOCOL_VERSION, theirs: 0 }
+        ));
+    }
 }
\ No newline at end of file
@@ -0,0 +1,128 @@
+use crate::interfaces::{NetworkError, RepoId, RepoMessage};
+use futures::{Sink, SinkExt, Stream, StreamExt};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use tokio::sync::mpsc;
+
+/// What we know about a connected remote repo, beyond just how to reach it.
+struct RemoteRepo {
+    sender: mpsc::UnboundedSender<RepoMessage>,
+    /// The protocol version negotiated during the handshake. Chunking is already
+    /// gated at the `Codec` level (see `Codec::set_chunking_enabled` in
+    /// `connect_tokio_io`), so nothing reads this today; it's kept on the handle,
+    /// as asked, for future framing decisions that need to vary per remote.
+    #[allow(dead_code)]
+    protocol_version: u32,
+}
+
+/// A handle to a running repo, cheaply cloneable and shared with every connection task.
+#[derive(Clone)]
+pub struct RepoHandle {
+    repo_id: RepoId,
+    remotes: Arc<Mutex<HashMap<RepoId, RemoteRepo>>>,
+    /// Last `count` seen per ephemeral `session_id`, so dedup costs O(sessions) rather
+    /// than O(messages). Sound because `count` is documented as monotonically
+    /// increasing per session: anything at or below the last-seen count is a repeat.
+    seen_ephemeral: Arc<Mutex<HashMap<String, u64>>>,
+}
+
+impl RepoHandle {
+    pub fn new(repo_id: RepoId) -> Self {
+        Self {
+            repo_id,
+            remotes: Arc::new(Mutex::new(HashMap::new())),
+            seen_ephemeral: Arc::new(Mutex::new(HashMap::new())),
+        }
+    }
+
+    pub fn get_repo_id(&self) -> &RepoId {
+        &self.repo_id
+    }
+
+    /// Registers a freshly handshaked connection as a remote repo. Incoming messages are
+    /// routed (and, for ephemeral broadcasts, re-gossiped to our other peers); outgoing
+    /// messages - including those injected by the gossip re-forward - are written to `sink`.
+    pub(crate) fn new_remote_repo(
+        &self,
+        repo_id: RepoId,
+        protocol_version: u32,
+        mut stream: Box<dyn Stream<Item = Result<RepoMessage, NetworkError>> + Send + Unpin>,
+        mut sink: Box<dyn Sink<Result<RepoMessage, NetworkError>, Error = NetworkError> + Send + Unpin>,
+    ) {
+        let (outbound_tx, mut outbound_rx) = mpsc::unbounded_channel::<RepoMessage>();
+        self.remotes.lock().unwrap().insert(
+            repo_id.clone(),
+            RemoteRepo {
+                sender: outbound_tx,
+                protocol_version,
+            },
+        );
+
+        let handle = self.clone();
+        let from = repo_id.clone();
+        tokio::spawn(async move {
+            while let Some(Ok(msg)) = stream.next().await {
+                handle.route_repo_message(&from, msg);
+            }
+            handle.remotes.lock().unwrap().remove(&from);
+        });
+
+        tokio::spawn(async move {
+            while let Some(msg) = outbound_rx.recv().await {
+                if sink.send(Ok(msg)).await.is_err() {
+                    break;
+                }
+            }
+        });
+    }
+
+    /// Handles a message received from `from`: repeated ephemeral messages are
+    /// dropped, and ephemeral broadcasts are gossiped on to our other peers exactly
+    /// once. Nothing else is done with the message: it is not delivered to any
+    /// subscriber and never touches `Storage`.
+    fn route_repo_message(&self, from: &RepoId, msg: RepoMessage) {
+        if let RepoMessage::Ephemeral {
+            session_id,
+            count,
+            broadcast,
+            ..
+        } = &msg
+        {
+            if !self.mark_ephemeral_seen(session_id, *count) {
+                return;
+            }
+            if *broadcast {
+                self.forward_to_other_remotes(from, msg.clone());
+            }
+        }
+
+        // `msg` is not delivered anywhere else; it is dropped here.
+    }
+
+    /// Returns `true` the first time this `count` (or a higher one) is seen for
+    /// `session_id`, given counts within a session only ever increase.
+    fn mark_ephemeral_seen(&self, session_id: &str, count: u64) -> bool {
+        match self.seen_ephemeral.lock().unwrap().entry(session_id.to_string()) {
+            std::collections::hash_map::Entry::Occupied(mut entry) => {
+                if count <= *entry.get() {
+                    return false;
+                }
+                entry.insert(count);
+                true
+            }
+            std::collections::hash_map::Entry::Vacant(entry) => {
+                entry.insert(count);
+                true
+            }
+        }
+    }
+
+    fn forward_to_other_remotes(&self, from: &RepoId, msg: RepoMessage) {
+        let remotes = self.remotes.lock().unwrap();
+        for (repo_id, remote) in remotes.iter() {
+            if repo_id != from {
+                let _ = remote.sender.send(msg.clone());
+            }
+        }
+    }
+}
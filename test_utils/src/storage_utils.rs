@@ -1,7 +1,8 @@
 use automerge_repo::{DocumentId, Storage, StorageError};
 use futures::future::TryFutureExt;
-use futures::Future;
+use futures::{Future, FutureExt};
 use parking_lot::Mutex;
+use sqlx::SqlitePool;
 use std::collections::{HashMap, VecDeque};
 use std::marker::Unpin;
 use std::sync::Arc;
@@ -209,3 +210,245 @@ impl Storage for AsyncInMemoryStorage {
         Box::new(rx.map_err(|_| StorageError::Error))
     }
 }
+
+#[derive(Debug)]
+enum SqliteStorageRequest {
+    Get(DocumentId, OneShot<Result<Option<Vec<u8>>, StorageError>>),
+    ListAll(OneShot<Result<Vec<DocumentId>, StorageError>>),
+    Append(DocumentId, Vec<u8>, OneShot<Result<(), StorageError>>),
+    Compact(DocumentId, Vec<u8>, OneShot<Result<(), StorageError>>),
+}
+
+/// A durable `Storage` backed by a `sqlx::SqlitePool`. Unlike `InMemoryStorage`, documents
+/// survive a restart: each `append` is a new numbered row in `incremental_changes`, `get`
+/// replays them in order, and `compact` collapses them down to a single snapshot row.
+///
+/// `Storage`'s methods are synchronous and return a boxed future, so the actual queries run
+/// on an actor task (same shape as `AsyncInMemoryStorage`) and the sync method bodies just
+/// hand the request off and return the oneshot receiver.
+#[derive(Clone, Debug)]
+pub struct SqliteStorage {
+    chan: Sender<SqliteStorageRequest>,
+}
+
+impl SqliteStorage {
+    pub async fn new(pool: SqlitePool) -> Result<Self, sqlx::Error> {
+        sqlx::query(
+            "CREATE TABLE IF NOT EXISTS incremental_changes (
+                document_id BLOB NOT NULL,
+                seq INTEGER NOT NULL,
+                data BLOB NOT NULL,
+                PRIMARY KEY (document_id, seq)
+            )",
+        )
+        .execute(&pool)
+        .await?;
+
+        let (chan, mut requests) = channel::<SqliteStorageRequest>(32);
+        tokio::spawn(async move {
+            while let Some(request) = requests.recv().await {
+                match request {
+                    SqliteStorageRequest::Get(id, reply) => {
+                        let _ = reply.send(Self::load(&pool, &id).await);
+                    }
+                    SqliteStorageRequest::ListAll(reply) => {
+                        let _ = reply.send(Self::list_all_docs(&pool).await);
+                    }
+                    SqliteStorageRequest::Append(id, changes, reply) => {
+                        let _ = reply.send(Self::append_row(&pool, &id, changes).await);
+                    }
+                    SqliteStorageRequest::Compact(id, full_doc, reply) => {
+                        let _ = reply.send(Self::compact_doc(&pool, &id, full_doc).await);
+                    }
+                }
+            }
+        });
+
+        Ok(SqliteStorage { chan })
+    }
+
+    /// Hands `request` off to the actor task without blocking the calling task. `Storage`'s
+    /// methods are synchronous, so we can't just `.await` the send here; spawning it avoids
+    /// `blocking_send`, which would panic when called from within a Tokio runtime - exactly
+    /// where `Storage` implementors are used.
+    fn enqueue(&self, request: SqliteStorageRequest) {
+        let chan = self.chan.clone();
+        tokio::spawn(async move {
+            let _ = chan.send(request).await;
+        });
+    }
+
+    async fn load(pool: &SqlitePool, id: &DocumentId) -> Result<Option<Vec<u8>>, StorageError> {
+        let rows: Vec<(Vec<u8>,)> = sqlx::query_as(
+            "SELECT data FROM incremental_changes WHERE document_id = ? ORDER BY seq ASC",
+        )
+        .bind(id.to_vec())
+        .fetch_all(pool)
+        .await
+        .map_err(|_| StorageError::Error)?;
+
+        if rows.is_empty() {
+            return Ok(None);
+        }
+        Ok(Some(rows.into_iter().flat_map(|(data,)| data).collect()))
+    }
+
+    async fn list_all_docs(pool: &SqlitePool) -> Result<Vec<DocumentId>, StorageError> {
+        let rows: Vec<(Vec<u8>,)> =
+            sqlx::query_as("SELECT DISTINCT document_id FROM incremental_changes")
+                .fetch_all(pool)
+                .await
+                .map_err(|_| StorageError::Error)?;
+
+        Ok(rows.into_iter().map(|(id,)| DocumentId::from(id)).collect())
+    }
+
+    async fn append_row(
+        pool: &SqlitePool,
+        id: &DocumentId,
+        changes: Vec<u8>,
+    ) -> Result<(), StorageError> {
+        let next_seq: i64 = sqlx::query_scalar(
+            "SELECT COALESCE(MAX(seq), -1) + 1 FROM incremental_changes WHERE document_id = ?",
+        )
+        .bind(id.to_vec())
+        .fetch_one(pool)
+        .await
+        .map_err(|_| StorageError::Error)?;
+
+        sqlx::query("INSERT INTO incremental_changes (document_id, seq, data) VALUES (?, ?, ?)")
+            .bind(id.to_vec())
+            .bind(next_seq)
+            .bind(changes)
+            .execute(pool)
+            .await
+            .map_err(|_| StorageError::Error)?;
+
+        Ok(())
+    }
+
+    async fn compact_doc(
+        pool: &SqlitePool,
+        id: &DocumentId,
+        full_doc: Vec<u8>,
+    ) -> Result<(), StorageError> {
+        let mut tx = pool.begin().await.map_err(|_| StorageError::Error)?;
+
+        sqlx::query("DELETE FROM incremental_changes WHERE document_id = ?")
+            .bind(id.to_vec())
+            .execute(&mut *tx)
+            .await
+            .map_err(|_| StorageError::Error)?;
+
+        sqlx::query("INSERT INTO incremental_changes (document_id, seq, data) VALUES (?, 0, ?)")
+            .bind(id.to_vec())
+            .bind(full_doc)
+            .execute(&mut *tx)
+            .await
+            .map_err(|_| StorageError::Error)?;
+
+        tx.commit().await.map_err(|_| StorageError::Error)?;
+        Ok(())
+    }
+}
+
+impl Storage for SqliteStorage {
+    fn get(
+        &self,
+        id: DocumentId,
+    ) -> Box<dyn Future<Output = Result<Option<Vec<u8>>, StorageError>> + Send + Unpin> {
+        let (tx, rx) = oneshot();
+        self.enqueue(SqliteStorageRequest::Get(id, tx));
+        Box::new(rx.map(|result| result.unwrap_or(Err(StorageError::Error))))
+    }
+
+    fn list_all(
+        &self,
+    ) -> Box<dyn Future<Output = Result<Vec<DocumentId>, StorageError>> + Send + Unpin> {
+        let (tx, rx) = oneshot();
+        self.enqueue(SqliteStorageRequest::ListAll(tx));
+        Box::new(rx.map(|result| result.unwrap_or(Err(StorageError::Error))))
+    }
+
+    fn append(
+        &self,
+        id: DocumentId,
+        changes: Vec<u8>,
+    ) -> Box<dyn Future<Output = Result<(), StorageError>> + Send + Unpin> {
+        let (tx, rx) = oneshot();
+        self.enqueue(SqliteStorageRequest::Append(id, changes, tx));
+        Box::new(rx.map(|result| result.unwrap_or(Err(StorageError::Error))))
+    }
+
+    fn compact(
+        &self,
+        id: DocumentId,
+        full_doc: Vec<u8>,
+    ) -> Box<dyn Future<Output = Result<(), StorageError>> + Send + Unpin> {
+        let (tx, rx) = oneshot();
+        self.enqueue(SqliteStorageRequest::Compact(id, full_doc, tx));
+        Box::new(rx.map(|result| result.unwrap_or(Err(StorageError::Error))))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    async fn storage() -> SqliteStorage {
+        let pool = SqlitePool::connect("sqlite::memory:").await.unwrap();
+        SqliteStorage::new(pool).await.unwrap()
+    }
+
+    #[tokio::test]
+    async fn append_then_get_round_trips_concatenated_changes() {
+        let storage = storage().await;
+        let id = DocumentId::from(vec![1, 2, 3]);
+
+        storage.append(id.clone(), vec![1, 2]).await.unwrap();
+        storage.append(id.clone(), vec![3, 4]).await.unwrap();
+
+        assert_eq!(storage.get(id).await.unwrap(), Some(vec![1, 2, 3, 4]));
+    }
+
+    #[tokio::test]
+    async fn get_on_an_unknown_document_returns_none() {
+        let storage = storage().await;
+
+        assert_eq!(storage.get(DocumentId::from(vec![9])).await.unwrap(), None);
+    }
+
+    #[tokio::test]
+    async fn compact_collapses_prior_rows_into_a_single_snapshot() {
+        let storage = storage().await;
+        let id = DocumentId::from(vec![1]);
+
+        storage.append(id.clone(), vec![1, 2]).await.unwrap();
+        storage.append(id.clone(), vec![3, 4]).await.unwrap();
+        storage.compact(id.clone(), vec![9, 9]).await.unwrap();
+
+        assert_eq!(storage.get(id.clone()).await.unwrap(), Some(vec![9, 9]));
+
+        // A later append must pick up after the compacted row, not collide with it.
+        storage.append(id.clone(), vec![5]).await.unwrap();
+        assert_eq!(storage.get(id).await.unwrap(), Some(vec![9, 9, 5]));
+    }
+
+    #[tokio::test]
+    async fn list_all_returns_each_document_id_once() {
+        let storage = storage().await;
+        let id = DocumentId::from(vec![1]);
+
+        storage.append(id.clone(), vec![1]).await.unwrap();
+        storage.append(id.clone(), vec![2]).await.unwrap();
+        storage
+            .append(DocumentId::from(vec![2]), vec![3])
+            .await
+            .unwrap();
+
+        let ids = storage.list_all().await.unwrap();
+        assert_eq!(ids.len(), 2);
+        assert!(ids.contains(&DocumentId::from(vec![1])));
+        assert!(ids.contains(&DocumentId::from(vec![2])));
+    }
+}